@@ -0,0 +1,307 @@
+// Copyright 2015, Igor Shaula
+// Licensed under the MIT License <LICENSE or
+// http://opensource.org/licenses/MIT>. This file
+// may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Deserialization of rust structures from registry keys
+use std::error::Error;
+use std::fmt;
+use std::io;
+use super::RegKey;
+use super::enums::KEY_READ;
+use super::types::FromRegValue;
+
+use serde::de::{self, Deserialize, IntoDeserializer};
+
+#[derive(Debug)]
+pub enum DecoderError {
+    Io(io::Error),
+    FieldMissing(String),
+    Message(String),
+}
+
+pub type DecodeResult<T> = Result<T, DecoderError>;
+
+impl fmt::Display for DecoderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecoderError::Io(ref e) => e.fmt(f),
+            DecoderError::FieldMissing(ref name) => write!(f, "missing field `{}`", name),
+            DecoderError::Message(ref s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl Error for DecoderError {
+    fn description(&self) -> &str {
+        "decoder error"
+    }
+}
+
+impl de::Error for DecoderError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DecoderError::Message(msg.to_string())
+    }
+}
+
+impl From<io::Error> for DecoderError {
+    fn from(err: io::Error) -> DecoderError {
+        DecoderError::Io(err)
+    }
+}
+
+/// A serde `Deserializer` that reads a struct's (or map's) fields from the
+/// named values and subkeys of a `RegKey`.
+pub struct Decoder<'a> {
+    key: &'a RegKey,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn from_key(key: &'a RegKey) -> Decoder<'a> {
+        Decoder { key }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Decoder<'a> {
+    type Error = DecoderError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> DecodeResult<V::Value> {
+        visitor.visit_map(StructAccess { key: self.key, fields: fields.iter(), current: None })
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        let mut names = self
+            .key
+            .enum_values()
+            .map(|r| r.map(|(name, _)| name))
+            .collect::<io::Result<Vec<String>>>()?;
+        // Struct- or map-valued entries were written as subkeys, not values.
+        for subkey_name in self.key.enum_keys() {
+            names.push(subkey_name?);
+        }
+        visitor.visit_map(MapAccess { key: self.key, names: names.into_iter(), current: None })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct identifier ignored_any enum
+    }
+}
+
+/// Feeds `Deserialize::deserialize` a field's `FromRegValue` reading for
+/// scalars, or recurses into a nested subkey's `Decoder` for anything else.
+struct FieldDeserializer<'a> {
+    key: &'a RegKey,
+    name: String,
+}
+
+impl<'a> FieldDeserializer<'a> {
+    /// Reads this field's raw value, turning a not-found error into the
+    /// friendlier `DecoderError::FieldMissing` rather than the raw OS error.
+    fn get_raw_value(&self) -> DecodeResult<super::RegValue> {
+        self.key.get_raw_value(&self.name).map_err(|err| self.missing_or_io(err))
+    }
+
+    fn missing_or_io(&self, err: io::Error) -> DecoderError {
+        if err.kind() == io::ErrorKind::NotFound {
+            DecoderError::FieldMissing(self.name.clone())
+        } else {
+            DecoderError::Io(err)
+        }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldDeserializer<'a> {
+    type Error = DecoderError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        if let Ok(subkey) = self.key.open_subkey(&self.name, KEY_READ) {
+            return Decoder::from_key(&subkey).deserialize_any(visitor);
+        }
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        let value = u32::from_reg_value(self.get_raw_value()?)?;
+        visitor.visit_u32(value)
+    }
+
+    fn deserialize_u64<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        let value = u64::from_reg_value(self.get_raw_value()?)?;
+        visitor.visit_u64(value)
+    }
+
+    fn deserialize_string<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        let value = String::from_reg_value(self.get_raw_value()?)?;
+        visitor.visit_string(value)
+    }
+
+    fn deserialize_str<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        let value = Vec::<u8>::from_reg_value(self.get_raw_value()?)?;
+        visitor.visit_byte_buf(value)
+    }
+
+    fn deserialize_bytes<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        // Present as either a named value or, for nested structs/maps, a subkey.
+        let present = self.key.get_raw_value(&self.name).is_ok()
+            || self.key.open_subkey(&self.name, KEY_READ).is_ok();
+        if present {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> DecodeResult<V::Value> {
+        let subkey = self.key.open_subkey(&self.name, KEY_READ).map_err(|err| self.missing_or_io(err))?;
+        Decoder::from_key(&subkey).deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> DecodeResult<V::Value> {
+        let subkey = self.key.open_subkey(&self.name, KEY_READ).map_err(|err| self.missing_or_io(err))?;
+        Decoder::from_key(&subkey).deserialize_map(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 f32 f64 char unit unit_struct newtype_struct
+        seq tuple tuple_struct identifier ignored_any enum
+    }
+}
+
+/// Drives visiting a struct's declared `fields` in order, skipping over any
+/// registry entry not named as one of them.
+struct StructAccess<'a> {
+    key: &'a RegKey,
+    fields: std::slice::Iter<'static, &'static str>,
+    current: Option<String>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for StructAccess<'a> {
+    type Error = DecoderError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> DecodeResult<Option<K::Value>> {
+        match self.fields.next() {
+            Some(field) => {
+                self.current = Some((*field).to_owned());
+                seed.deserialize(IntoDeserializer::<DecoderError>::into_deserializer(*field))
+                    .map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> DecodeResult<V::Value> {
+        let name = self.current.take().ok_or_else(|| DecoderError::Message("value requested before key".to_owned()))?;
+        seed.deserialize(FieldDeserializer { key: self.key, name })
+    }
+}
+
+/// Drives visiting every named value and subkey actually present under a key
+/// (used for `HashMap`-shaped fields, where the field set isn't known ahead
+/// of time).
+struct MapAccess<'a> {
+    key: &'a RegKey,
+    names: std::vec::IntoIter<String>,
+    current: Option<String>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for MapAccess<'a> {
+    type Error = DecoderError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> DecodeResult<Option<K::Value>> {
+        match self.names.next() {
+            Some(name) => {
+                self.current = Some(name.clone());
+                seed.deserialize(IntoDeserializer::<DecoderError>::into_deserializer(name))
+                    .map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> DecodeResult<V::Value> {
+        let name = self.current.take().ok_or_else(|| DecoderError::Message("value requested before key".to_owned()))?;
+        seed.deserialize(FieldDeserializer { key: self.key, name })
+    }
+}
+
+impl RegKey {
+    /// Deserializes the named values and subkeys directly under this key
+    /// into a `T`. Missing fields fail unless the field's type is `Option`.
+    pub fn decode<T: for<'de> Deserialize<'de>>(&self) -> DecodeResult<T> {
+        T::deserialize(Decoder::from_key(self))
+    }
+}
+
+#[cfg(windows)]
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+    use super::super::enums::*;
+    use super::super::RegKey;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Nested {
+        name: String,
+        count: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Outer {
+        id: u32,
+        nested: Nested,
+        maybe_present: Option<Nested>,
+        maybe_absent: Option<Nested>,
+    }
+
+    #[test]
+    fn encode_decode_round_trip_with_option_nested_struct() {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let path = "Software\\WinregRsTests\\encode_decode_round_trip_with_option_nested_struct";
+        hkcu.delete_subkey_all(path).ok();
+        let key = hkcu.create_subkey(path, KEY_ALL_ACCESS).unwrap();
+
+        let value = Outer {
+            id: 42,
+            nested: Nested { name: "plain".to_owned(), count: 1 },
+            maybe_present: Some(Nested { name: "present".to_owned(), count: 2 }),
+            maybe_absent: None,
+        };
+        key.encode(&value).unwrap();
+
+        let decoded: Outer = key.decode().unwrap();
+        assert_eq!(decoded, value);
+
+        hkcu.delete_subkey_all(path).unwrap();
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2015, Igor Shaula
+// Licensed under the MIT License <LICENSE or
+// http://opensource.org/licenses/MIT>. This file
+// may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serialization of rust structures into registry keys
+use std::error::Error;
+use std::fmt;
+use std::io;
+use super::RegKey;
+use super::enums::KEY_ALL_ACCESS;
+use super::transaction::TransactedRegKey;
+use super::types::ToRegValue;
+
+use serde::ser::{self, Serialize};
+
+#[derive(Debug)]
+pub enum EncoderError {
+    Io(io::Error),
+    NoFieldName,
+    SerializerNotImplemented,
+    Message(String),
+}
+
+pub type EncodeResult<T> = Result<T, EncoderError>;
+
+impl fmt::Display for EncoderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncoderError::Io(ref e) => e.fmt(f),
+            EncoderError::NoFieldName => write!(f, "field name is required for this type"),
+            EncoderError::SerializerNotImplemented => {
+                write!(f, "this serde type is not supported by the registry encoder")
+            },
+            EncoderError::Message(ref s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl Error for EncoderError {
+    fn description(&self) -> &str {
+        "encoder error"
+    }
+}
+
+impl ser::Error for EncoderError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        EncoderError::Message(msg.to_string())
+    }
+}
+
+impl From<io::Error> for EncoderError {
+    fn from(err: io::Error) -> EncoderError {
+        EncoderError::Io(err)
+    }
+}
+
+// Everything but struct/map fields and scalars routed through `ToRegValue`
+// is unsupported: the registry has no notion of a sequence or an enum
+// variant's payload, so there is no lossless mapping for those shapes.
+macro_rules! unsupported {
+    ($($name:ident ( $($arg:ident : $arg_ty:ty),* ) -> $ret:ty;)*) => {
+        $(
+            fn $name(self, $(#[allow(unused)] $arg: $arg_ty),*) -> EncodeResult<$ret> {
+                Err(EncoderError::SerializerNotImplemented)
+            }
+        )*
+    };
+}
+
+enum PlainKey<'a> {
+    Borrowed(&'a RegKey),
+    Owned(RegKey),
+}
+
+impl<'a> PlainKey<'a> {
+    fn as_ref(&self) -> &RegKey {
+        match *self {
+            PlainKey::Borrowed(k) => k,
+            PlainKey::Owned(ref k) => k,
+        }
+    }
+}
+
+enum TransactedKey<'a> {
+    Borrowed(&'a TransactedRegKey<'a>),
+    Owned(TransactedRegKey<'a>),
+}
+
+impl<'a> TransactedKey<'a> {
+    fn as_ref(&self) -> &TransactedRegKey<'a> {
+        match *self {
+            TransactedKey::Borrowed(k) => k,
+            TransactedKey::Owned(ref k) => k,
+        }
+    }
+}
+
+enum EncoderKey<'a> {
+    Plain(PlainKey<'a>),
+    Transacted(TransactedKey<'a>),
+}
+
+/// A serde `Serializer` that writes a struct's (or map's) fields as named
+/// values under a `RegKey`, recursing into a freshly-created subkey for any
+/// field that is itself a struct or map.
+pub struct Encoder<'a> {
+    key: EncoderKey<'a>,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn from_key(key: &'a RegKey) -> Encoder<'a> {
+        Encoder { key: EncoderKey::Plain(PlainKey::Borrowed(key)) }
+    }
+
+    pub fn from_transacted_key(key: &'a TransactedRegKey<'a>) -> Encoder<'a> {
+        Encoder { key: EncoderKey::Transacted(TransactedKey::Borrowed(key)) }
+    }
+
+    fn subkey(&self, name: &str) -> EncodeResult<Encoder<'a>> {
+        Ok(match self.key {
+            EncoderKey::Plain(ref k) => {
+                Encoder { key: EncoderKey::Plain(PlainKey::Owned(k.as_ref().create_subkey(name, KEY_ALL_ACCESS)?)) }
+            },
+            EncoderKey::Transacted(ref k) => {
+                Encoder { key: EncoderKey::Transacted(TransactedKey::Owned(k.as_ref().create_subkey(name)?)) }
+            },
+        })
+    }
+
+    fn set_value<T: ToRegValue>(&self, name: &str, value: &T) -> EncodeResult<()> {
+        match self.key {
+            EncoderKey::Plain(ref k) => k.as_ref().set_value(name, value)?,
+            EncoderKey::Transacted(ref k) => k.as_ref().set_value(name, value)?,
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for Encoder<'a> {
+    type Ok = ();
+    type Error = EncoderError;
+    type SerializeSeq = ser::Impossible<(), EncoderError>;
+    type SerializeTuple = ser::Impossible<(), EncoderError>;
+    type SerializeTupleStruct = ser::Impossible<(), EncoderError>;
+    type SerializeTupleVariant = ser::Impossible<(), EncoderError>;
+    type SerializeMap = StructEncoder<'a>;
+    type SerializeStruct = StructEncoder<'a>;
+    type SerializeStructVariant = ser::Impossible<(), EncoderError>;
+
+    fn serialize_none(self) -> EncodeResult<()> { Ok(()) }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> EncodeResult<()> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> EncodeResult<()> { Ok(()) }
+    fn serialize_unit_struct(self, _name: &'static str) -> EncodeResult<()> { Ok(()) }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> EncodeResult<()> {
+        value.serialize(self)
+    }
+    fn serialize_map(self, _len: Option<usize>) -> EncodeResult<StructEncoder<'a>> {
+        Ok(StructEncoder { encoder: self, field: None })
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> EncodeResult<StructEncoder<'a>> {
+        Ok(StructEncoder { encoder: self, field: None })
+    }
+
+    unsupported! {
+        serialize_bool(v: bool) -> ();
+        serialize_i8(v: i8) -> ();
+        serialize_i16(v: i16) -> ();
+        serialize_i32(v: i32) -> ();
+        serialize_i64(v: i64) -> ();
+        serialize_u8(v: u8) -> ();
+        serialize_u16(v: u16) -> ();
+        serialize_u32(v: u32) -> ();
+        serialize_u64(v: u64) -> ();
+        serialize_f32(v: f32) -> ();
+        serialize_f64(v: f64) -> ();
+        serialize_char(v: char) -> ();
+        serialize_str(v: &str) -> ();
+        serialize_bytes(v: &[u8]) -> ();
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> ();
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(name: &'static str, index: u32, variant: &'static str, len: usize) -> Self::SerializeTupleVariant;
+        serialize_struct_variant(name: &'static str, index: u32, variant: &'static str, len: usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> EncodeResult<()> {
+        Err(EncoderError::SerializerNotImplemented)
+    }
+}
+
+/// Walks a struct's or map's fields. A field whose value serializes as a
+/// scalar becomes a named value on this key; a field whose value is itself a
+/// struct or map recurses into a freshly-created subkey of the same name.
+pub struct StructEncoder<'a> {
+    encoder: Encoder<'a>,
+    field: Option<String>,
+}
+
+impl<'a> ser::SerializeStruct for StructEncoder<'a> {
+    type Ok = ();
+    type Error = EncoderError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> EncodeResult<()> {
+        self.field = Some(key.to_owned());
+        value.serialize(FieldEncoder { parent: self })
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for StructEncoder<'a> {
+    type Ok = ();
+    type Error = EncoderError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> EncodeResult<()> {
+        self.field = Some(key.serialize(MapKeyEncoder)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        value.serialize(FieldEncoder { parent: self })
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        Ok(())
+    }
+}
+
+/// Turns a map key back into a `String`; only string-like keys are supported
+/// since registry value/subkey names are themselves strings.
+struct MapKeyEncoder;
+
+impl ser::Serializer for MapKeyEncoder {
+    type Ok = String;
+    type Error = EncoderError;
+    type SerializeSeq = ser::Impossible<String, EncoderError>;
+    type SerializeTuple = ser::Impossible<String, EncoderError>;
+    type SerializeTupleStruct = ser::Impossible<String, EncoderError>;
+    type SerializeTupleVariant = ser::Impossible<String, EncoderError>;
+    type SerializeMap = ser::Impossible<String, EncoderError>;
+    type SerializeStruct = ser::Impossible<String, EncoderError>;
+    type SerializeStructVariant = ser::Impossible<String, EncoderError>;
+
+    fn serialize_str(self, v: &str) -> EncodeResult<String> { Ok(v.to_owned()) }
+
+    unsupported! {
+        serialize_bool(v: bool) -> String;
+        serialize_i8(v: i8) -> String;
+        serialize_i16(v: i16) -> String;
+        serialize_i32(v: i32) -> String;
+        serialize_i64(v: i64) -> String;
+        serialize_u8(v: u8) -> String;
+        serialize_u16(v: u16) -> String;
+        serialize_u32(v: u32) -> String;
+        serialize_u64(v: u64) -> String;
+        serialize_f32(v: f32) -> String;
+        serialize_f64(v: f64) -> String;
+        serialize_char(v: char) -> String;
+        serialize_bytes(v: &[u8]) -> String;
+        serialize_unit() -> String;
+        serialize_unit_struct(name: &'static str) -> String;
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> String;
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(name: &'static str, index: u32, variant: &'static str, len: usize) -> Self::SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> Self::SerializeMap;
+        serialize_struct(name: &'static str, len: usize) -> Self::SerializeStruct;
+        serialize_struct_variant(name: &'static str, index: u32, variant: &'static str, len: usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_none(self) -> EncodeResult<String> { Err(EncoderError::SerializerNotImplemented) }
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> EncodeResult<String> {
+        Err(EncoderError::SerializerNotImplemented)
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> EncodeResult<String> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> EncodeResult<String> {
+        Err(EncoderError::SerializerNotImplemented)
+    }
+}
+
+/// Serializes a single field/entry's value: scalars become a named value on
+/// the parent key, structs/maps recurse into a subkey named after the field.
+struct FieldEncoder<'a, 'b: 'a> {
+    parent: &'b mut StructEncoder<'a>,
+}
+
+impl<'a, 'b> FieldEncoder<'a, 'b> {
+    fn field_name(&self) -> EncodeResult<String> {
+        self.parent.field.clone().ok_or(EncoderError::NoFieldName)
+    }
+}
+
+macro_rules! forward_scalar {
+    ($name:ident, $t:ty) => {
+        fn $name(self, v: $t) -> EncodeResult<()> {
+            let name = self.field_name()?;
+            self.parent.encoder.set_value(&name, &v)
+        }
+    };
+}
+
+impl<'a, 'b> ser::Serializer for FieldEncoder<'a, 'b> {
+    type Ok = ();
+    type Error = EncoderError;
+    type SerializeSeq = ser::Impossible<(), EncoderError>;
+    type SerializeTuple = ser::Impossible<(), EncoderError>;
+    type SerializeTupleStruct = ser::Impossible<(), EncoderError>;
+    type SerializeTupleVariant = ser::Impossible<(), EncoderError>;
+    type SerializeMap = StructEncoder<'a>;
+    type SerializeStruct = StructEncoder<'a>;
+    type SerializeStructVariant = ser::Impossible<(), EncoderError>;
+
+    forward_scalar!(serialize_u32, u32);
+    forward_scalar!(serialize_u64, u64);
+
+    fn serialize_str(self, v: &str) -> EncodeResult<()> {
+        let name = self.field_name()?;
+        self.parent.encoder.set_value(&name, &v.to_owned())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> EncodeResult<()> {
+        let name = self.field_name()?;
+        self.parent.encoder.set_value(&name, &v.to_owned())
+    }
+    fn serialize_none(self) -> EncodeResult<()> { Ok(()) }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> EncodeResult<()> {
+        value.serialize(self)
+    }
+    fn serialize_map(self, len: Option<usize>) -> EncodeResult<StructEncoder<'a>> {
+        self.serialize_struct("", len.unwrap_or(0))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> EncodeResult<StructEncoder<'a>> {
+        let name = self.field_name()?;
+        let subkey_encoder = self.parent.encoder.subkey(&name)?;
+        Ok(StructEncoder { encoder: subkey_encoder, field: None })
+    }
+
+    unsupported! {
+        serialize_bool(v: bool) -> ();
+        serialize_i8(v: i8) -> ();
+        serialize_i16(v: i16) -> ();
+        serialize_i32(v: i32) -> ();
+        serialize_i64(v: i64) -> ();
+        serialize_u8(v: u8) -> ();
+        serialize_u16(v: u16) -> ();
+        serialize_f32(v: f32) -> ();
+        serialize_f64(v: f64) -> ();
+        serialize_char(v: char) -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(name: &'static str) -> ();
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> ();
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(name: &'static str, index: u32, variant: &'static str, len: usize) -> Self::SerializeTupleVariant;
+        serialize_struct_variant(name: &'static str, index: u32, variant: &'static str, len: usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> EncodeResult<()> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> EncodeResult<()> {
+        Err(EncoderError::SerializerNotImplemented)
+    }
+}
+
+impl RegKey {
+    /// Serializes `value`'s fields into named values (and nested subkeys for
+    /// nested structs/maps) under this key.
+    pub fn encode<T: Serialize>(&self, value: &T) -> EncodeResult<()> {
+        value.serialize(Encoder::from_key(self))
+    }
+}
+
+impl<'t> TransactedRegKey<'t> {
+    /// Same as `RegKey::encode`, but every write this produces becomes part
+    /// of the transaction this key was opened with.
+    pub fn encode<T: Serialize>(&self, value: &T) -> EncodeResult<()> {
+        value.serialize(Encoder::from_transacted_key(self))
+    }
+}
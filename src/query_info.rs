@@ -0,0 +1,135 @@
+// Copyright 2015, Igor Shaula
+// Licensed under the MIT License <LICENSE or
+// http://opensource.org/licenses/MIT>. This file
+// may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Querying a key's metadata via `RegQueryInfoKey`
+use std::io;
+use std::mem;
+use std::ptr;
+use std::time::{Duration, SystemTime};
+use super::RegKey;
+use super::winapi::shared::minwindef::DWORD;
+use super::winapi::um::minwinbase::FILETIME;
+use super::winapi::um::winreg as winapi_reg;
+
+/// Seconds between the `FILETIME` epoch (1601-01-01) and the Unix epoch
+/// (1970-01-01).
+const FILETIME_UNIX_EPOCH_DIFF_SECS: u64 = 11_644_473_600;
+
+/// Metadata about a registry key, as returned by `RegKey::query_info`.
+#[derive(Debug, Clone, Copy)]
+pub struct RegKeyMetadata {
+    pub sub_keys: u32,
+    pub max_sub_key_len: u32,
+    pub max_class_len: u32,
+    pub values: u32,
+    pub max_value_name_len: u32,
+    pub max_value_len: u32,
+    pub security_descriptor_len: u32,
+    /// The key's last-write time, as a Windows `FILETIME` (100-ns intervals
+    /// since 1601-01-01).
+    pub last_write_time: FILETIME,
+}
+
+impl RegKeyMetadata {
+    /// `last_write_time` as a single 64-bit count of 100-ns intervals since
+    /// 1601-01-01, the form Windows APIs pass it around in.
+    pub fn last_write_time_raw(&self) -> u64 {
+        (u64::from(self.last_write_time.dwHighDateTime) << 32)
+            | u64::from(self.last_write_time.dwLowDateTime)
+    }
+
+    /// `last_write_time` converted to a `SystemTime`, or `None` if it
+    /// predates the Unix epoch — which Windows can legitimately return (e.g.
+    /// a zeroed timestamp on a freshly created key, or a key in a hive that
+    /// never recorded one).
+    pub fn last_write_time(&self) -> Option<SystemTime> {
+        let intervals = self.last_write_time_raw();
+        let unix_secs = (intervals / 10_000_000).checked_sub(FILETIME_UNIX_EPOCH_DIFF_SECS)?;
+        let nanos = (intervals % 10_000_000) * 100;
+        Some(SystemTime::UNIX_EPOCH + Duration::new(unix_secs, nanos as u32))
+    }
+}
+
+impl RegKey {
+    /// Retrieves this key's subkey/value counts, the longest name/data
+    /// lengths among them, and its last-write time, via `RegQueryInfoKey`.
+    pub fn query_info(&self) -> io::Result<RegKeyMetadata> {
+        let mut sub_keys: DWORD = 0;
+        let mut max_sub_key_len: DWORD = 0;
+        let mut max_class_len: DWORD = 0;
+        let mut values: DWORD = 0;
+        let mut max_value_name_len: DWORD = 0;
+        let mut max_value_len: DWORD = 0;
+        let mut security_descriptor_len: DWORD = 0;
+        let mut last_write_time: FILETIME = unsafe { mem::zeroed() };
+
+        match unsafe {
+            winapi_reg::RegQueryInfoKeyW(
+                self.raw_hkey(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut sub_keys,
+                &mut max_sub_key_len,
+                &mut max_class_len,
+                &mut values,
+                &mut max_value_name_len,
+                &mut max_value_len,
+                &mut security_descriptor_len,
+                &mut last_write_time,
+            )
+        } {
+            0 => Ok(RegKeyMetadata {
+                sub_keys,
+                max_sub_key_len,
+                max_class_len,
+                values,
+                max_value_name_len,
+                max_value_len,
+                security_descriptor_len,
+                last_write_time,
+            }),
+            err => werr!(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata_with_last_write_time(last_write_time: FILETIME) -> RegKeyMetadata {
+        RegKeyMetadata {
+            sub_keys: 0,
+            max_sub_key_len: 0,
+            max_class_len: 0,
+            values: 0,
+            max_value_name_len: 0,
+            max_value_len: 0,
+            security_descriptor_len: 0,
+            last_write_time,
+        }
+    }
+
+    #[test]
+    fn zeroed_filetime_predates_unix_epoch() {
+        let metadata = metadata_with_last_write_time(FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 });
+        assert_eq!(metadata.last_write_time(), None);
+    }
+
+    #[test]
+    fn filetime_round_trips_to_known_system_time() {
+        // 2020-01-01T00:00:00Z, in 100-ns intervals since 1601-01-01.
+        let intervals: u64 = 132_223_104_000_000_000;
+        let metadata = metadata_with_last_write_time(FILETIME {
+            dwLowDateTime: intervals as u32,
+            dwHighDateTime: (intervals >> 32) as u32,
+        });
+        assert_eq!(metadata.last_write_time_raw(), intervals);
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_577_836_800);
+        assert_eq!(metadata.last_write_time(), Some(expected));
+    }
+}
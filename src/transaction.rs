@@ -0,0 +1,291 @@
+// Copyright 2015, Igor Shaula
+// Licensed under the MIT License <LICENSE or
+// http://opensource.org/licenses/MIT>. This file
+// may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Transactional registry operations via the Kernel Transaction Manager (KTM)
+use std::io;
+use std::ptr;
+use std::path::Path;
+use super::winapi::shared::minwindef::DWORD;
+use super::winapi::shared::winerror;
+use super::winapi::um::{handleapi, ktmw32, winnt, winreg as winapi_reg, winnt::REG_OPTION_NON_VOLATILE};
+use super::enums::*;
+use super::{RegKey, RegValue};
+use super::types::ToRegValue;
+
+/// A handle to a Windows KTM transaction.
+///
+/// Pass a `&Transaction` to any of `RegKey`'s `*_transacted` methods to make the
+/// change part of this transaction. Nothing is made durable until `commit()` is
+/// called; dropping a `Transaction` that was neither committed nor rolled back
+/// rolls it back, so an early return or a `?` partway through a sequence of
+/// changes can never leave the registry half-updated.
+pub struct Transaction {
+    handle: winnt::HANDLE,
+    done: bool,
+}
+
+impl Transaction {
+    /// Starts a new, uncommitted transaction.
+    pub fn new() -> io::Result<Transaction> {
+        unsafe {
+            let handle = ktmw32::CreateTransaction(
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+                0,
+                0,
+                0,
+                ptr::null_mut(),
+            );
+            if handle == handleapi::INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Transaction { handle, done: false })
+        }
+    }
+
+    pub(crate) fn raw_handle(&self) -> winnt::HANDLE {
+        self.handle
+    }
+
+    /// Makes all changes applied through this transaction durable.
+    pub fn commit(&mut self) -> io::Result<()> {
+        unsafe {
+            match ktmw32::CommitTransaction(self.handle) {
+                0 => Err(io::Error::last_os_error()),
+                _ => {
+                    self.done = true;
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Discards all changes applied through this transaction.
+    pub fn rollback(&mut self) -> io::Result<()> {
+        unsafe {
+            match ktmw32::RollbackTransaction(self.handle) {
+                0 => Err(io::Error::last_os_error()),
+                _ => {
+                    self.done = true;
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.done {
+                // Best-effort: we can't do anything useful with an error here
+                // since we're already unwinding/returning.
+                ktmw32::RollbackTransaction(self.handle);
+            }
+            handleapi::CloseHandle(self.handle);
+        }
+    }
+}
+
+impl RegKey {
+    /// Same as `create_subkey`, but the creation becomes part of `transaction`
+    /// and is only made durable once the transaction is committed.
+    ///
+    /// Returns a [`TransactedRegKey`] rather than a plain `RegKey`: per KTM,
+    /// only a handle obtained this way (or via `open_subkey_transacted`) has
+    /// its later writes automatically enlisted in `transaction` — a plain
+    /// `RegKey::set_value` call knows nothing about transactions at all. The
+    /// wrapper's `set_value`/`set_raw_value` are only available on a handle
+    /// that KTM actually covers.
+    pub fn create_subkey_transacted<'t, P: AsRef<Path>>(
+        &self,
+        path: P,
+        transaction: &'t Transaction,
+    ) -> io::Result<TransactedRegKey<'t>> {
+        self.create_subkey_transacted_with_flags(path, KEY_ALL_ACCESS, transaction)
+    }
+
+    /// Same as `create_subkey_with_flags`, but the creation becomes part of
+    /// `transaction`. See [`create_subkey_transacted`] for why this returns a
+    /// [`TransactedRegKey`].
+    pub fn create_subkey_transacted_with_flags<'t, P: AsRef<Path>>(
+        &self,
+        path: P,
+        perms: DWORD,
+        transaction: &'t Transaction,
+    ) -> io::Result<TransactedRegKey<'t>> {
+        let c_path = to_utf16(path.as_ref());
+        let mut new_hkey = 0 as winapi_reg::HKEY;
+        let mut disp: DWORD = 0;
+        match unsafe {
+            winapi_reg::RegCreateKeyTransactedW(
+                self.raw_hkey(),
+                c_path.as_ptr(),
+                0,
+                ptr::null_mut(),
+                REG_OPTION_NON_VOLATILE,
+                perms,
+                ptr::null_mut(),
+                &mut new_hkey,
+                &mut disp,
+                transaction.raw_handle(),
+                ptr::null_mut(),
+            )
+        } {
+            0 => Ok(TransactedRegKey { key: RegKey::from_raw_hkey(new_hkey), transaction }),
+            err => werr!(err),
+        }
+    }
+
+    /// Same as `open_subkey`, but the key is opened as part of `transaction`.
+    /// See [`create_subkey_transacted`] for why this returns a
+    /// [`TransactedRegKey`].
+    pub fn open_subkey_transacted<'t, P: AsRef<Path>>(
+        &self,
+        path: P,
+        transaction: &'t Transaction,
+    ) -> io::Result<TransactedRegKey<'t>> {
+        self.open_subkey_transacted_with_flags(path, KEY_READ, transaction)
+    }
+
+    /// Same as `open_subkey_with_flags`, but the key is opened as part of
+    /// `transaction`.
+    pub fn open_subkey_transacted_with_flags<'t, P: AsRef<Path>>(
+        &self,
+        path: P,
+        perms: DWORD,
+        transaction: &'t Transaction,
+    ) -> io::Result<TransactedRegKey<'t>> {
+        let c_path = to_utf16(path.as_ref());
+        let mut new_hkey = 0 as winapi_reg::HKEY;
+        match unsafe {
+            winapi_reg::RegOpenKeyTransactedW(
+                self.raw_hkey(),
+                c_path.as_ptr(),
+                0,
+                perms,
+                &mut new_hkey,
+                transaction.raw_handle(),
+                ptr::null_mut(),
+            )
+        } {
+            0 => Ok(TransactedRegKey { key: RegKey::from_raw_hkey(new_hkey), transaction }),
+            err => werr!(err),
+        }
+    }
+
+    /// Same as `delete_subkey`, but the deletion becomes part of `transaction`.
+    ///
+    /// Unlike the setters, `RegDeleteKeyTransactedW` takes `transaction`'s
+    /// handle as an explicit argument rather than relying on `self` having
+    /// been opened transacted, so this is sound to call on any `RegKey`.
+    pub fn delete_subkey_transacted<P: AsRef<Path>>(
+        &self,
+        path: P,
+        transaction: &Transaction,
+    ) -> io::Result<()> {
+        let c_path = to_utf16(path.as_ref());
+        match unsafe {
+            winapi_reg::RegDeleteKeyTransactedW(
+                self.raw_hkey(),
+                c_path.as_ptr(),
+                0,
+                0,
+                transaction.raw_handle(),
+                ptr::null_mut(),
+            )
+        } {
+            0 => Ok(()),
+            err => werr!(err),
+        }
+    }
+}
+
+/// A `RegKey` known to have been opened or created via
+/// `open_subkey_transacted`/`create_subkey_transacted`, so that KTM
+/// automatically enlists writes made through it in the transaction it was
+/// opened with. Its `set_raw_value`/`set_value` are the only setters that
+/// write inside a transaction; a plain `RegKey`, even one
+/// pointing at the same registry path, has no such association and would
+/// silently write outside the transaction.
+pub struct TransactedRegKey<'t> {
+    key: RegKey,
+    transaction: &'t Transaction,
+}
+
+impl<'t> TransactedRegKey<'t> {
+    /// The underlying key, for the read-only `RegKey` methods (`get_value`,
+    /// `enum_values`, ...) that don't need transaction-awareness.
+    pub fn as_reg_key(&self) -> &RegKey {
+        &self.key
+    }
+
+    /// Writes `value` as part of the transaction this key was opened with.
+    pub fn set_raw_value(&self, name: &str, value: &RegValue) -> io::Result<()> {
+        self.key.set_raw_value(name, value)
+    }
+
+    /// Writes `value` as part of the transaction this key was opened with.
+    pub fn set_value<T: ToRegValue>(&self, name: &str, value: &T) -> io::Result<()> {
+        self.set_raw_value(name, &value.to_reg_value())
+    }
+
+    /// Same as `RegKey::create_subkey_transacted`, but using the transaction
+    /// this key is already part of.
+    pub fn create_subkey<P: AsRef<Path>>(&self, path: P) -> io::Result<TransactedRegKey<'t>> {
+        self.key.create_subkey_transacted(path, self.transaction)
+    }
+
+    /// Same as `RegKey::open_subkey_transacted`, but using the transaction
+    /// this key is already part of.
+    pub fn open_subkey<P: AsRef<Path>>(&self, path: P) -> io::Result<TransactedRegKey<'t>> {
+        self.key.open_subkey_transacted(path, self.transaction)
+    }
+}
+
+#[cfg(windows)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::RegKey;
+
+    #[test]
+    fn dropping_uncommitted_transaction_rolls_back() {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let path = "Software\\WinregRsTests\\dropping_uncommitted_transaction_rolls_back";
+        hkcu.delete_subkey_all(path).ok();
+
+        {
+            let transaction = Transaction::new().unwrap();
+            let key = hkcu.create_subkey_transacted(path, &transaction).unwrap();
+            key.set_value("Test", &"written inside the transaction".to_owned()).unwrap();
+            // `transaction` is dropped here without being committed.
+        }
+
+        assert!(hkcu.open_subkey(path, KEY_READ).is_err());
+    }
+
+    #[test]
+    fn committed_transaction_is_visible() {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let path = "Software\\WinregRsTests\\committed_transaction_is_visible";
+        hkcu.delete_subkey_all(path).ok();
+
+        {
+            let mut transaction = Transaction::new().unwrap();
+            let key = hkcu.create_subkey_transacted(path, &transaction).unwrap();
+            key.set_value("Test", &"written inside the transaction".to_owned()).unwrap();
+            transaction.commit().unwrap();
+        }
+
+        let key = hkcu.open_subkey(path, KEY_READ).unwrap();
+        let value: String = key.get_value("Test").unwrap();
+        assert_eq!(value, "written inside the transaction");
+
+        hkcu.delete_subkey_all(path).unwrap();
+    }
+}
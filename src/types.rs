@@ -150,3 +150,213 @@ impl<'a> ToRegValue for &'a str {
         RegistryData::RegSz(self.to_owned()).to_reg_value()
     }
 }
+
+impl FromRegValue for Vec<u8> {
+    fn from_reg_value(val: RegValue) -> io::Result<Vec<u8>> {
+        match val.vtype {
+            REG_BINARY => Ok(val.bytes.clone()),
+            _ => werr!(winerror::ERROR_BAD_FILE_TYPE)
+        }
+    }
+}
+
+impl ToRegValue for Vec<u8> {
+    fn to_reg_value(&self) -> RegValue {
+        RegValue {
+            bytes: self.clone(),
+            vtype: REG_BINARY
+        }
+    }
+}
+
+impl<'a> ToRegValue for &'a [u8] {
+    fn to_reg_value(&self) -> RegValue {
+        RegValue {
+            bytes: self.to_vec(),
+            vtype: REG_BINARY
+        }
+    }
+}
+
+// Each of the `n` strings in a `REG_MULTI_SZ` contributes its own NUL
+// terminator, and the value as a whole adds one further NUL after the last
+// one. So splitting the whole buffer on NUL words always yields exactly
+// `n + 2` runs: the `n` strings themselves (which may legitimately be
+// empty), followed by the always-empty gap between the last string's own
+// terminator and the value's terminator, followed by the always-empty
+// tail after that final separator. Only those last two are ever artifacts
+// of `split` rather than real entries, regardless of what the real entries
+// contain.
+fn multi_sz_strings(words: &[u16]) -> Vec<&[u16]> {
+    let mut runs: Vec<&[u16]> = words.split(|&w| w == 0).collect();
+    runs.truncate(runs.len().saturating_sub(2));
+    runs
+}
+
+impl FromRegValue for Vec<String> {
+    fn from_reg_value(val: RegValue) -> io::Result<Vec<String>> {
+        let words = unsafe {
+            slice::from_raw_parts(val.bytes.as_ptr() as *const u16, val.bytes.len() / 2)
+        };
+        match val.vtype {
+            REG_MULTI_SZ => {
+                Ok(multi_sz_strings(words).into_iter().map(String::from_utf16_lossy).collect())
+            },
+            // A plain string isn't a list: it has only its own terminator,
+            // not the extra one `multi_sz_strings` expects, so read it as a
+            // single one-element "list" instead.
+            REG_SZ | REG_EXPAND_SZ => {
+                let content = words.split(|&w| w == 0).next().unwrap_or(&[]);
+                Ok(vec![String::from_utf16_lossy(content)])
+            },
+            _ => werr!(winerror::ERROR_BAD_FILE_TYPE)
+        }
+    }
+}
+
+impl ToRegValue for Vec<String> {
+    fn to_reg_value(&self) -> RegValue {
+        let mut words: Vec<u16> = Vec::new();
+        for s in self {
+            words.extend(to_utf16(s));
+        }
+        words.push(0); // terminate the list with an extra NUL
+        RegValue {
+            bytes: v16_to_v8(words),
+            vtype: REG_MULTI_SZ
+        }
+    }
+}
+
+impl<'a> ToRegValue for Vec<&'a str> {
+    fn to_reg_value(&self) -> RegValue {
+        let mut words: Vec<u16> = Vec::new();
+        for s in self {
+            words.extend(to_utf16(s));
+        }
+        words.push(0);
+        RegValue {
+            bytes: v16_to_v8(words),
+            vtype: REG_MULTI_SZ
+        }
+    }
+}
+
+#[cfg(windows)]
+impl FromRegValue for Vec<OsString> {
+    fn from_reg_value(val: RegValue) -> io::Result<Vec<OsString>> {
+        let words = unsafe {
+            slice::from_raw_parts(val.bytes.as_ptr() as *const u16, val.bytes.len() / 2)
+        };
+        match val.vtype {
+            REG_MULTI_SZ => {
+                Ok(multi_sz_strings(words).into_iter().map(OsString::from_wide).collect())
+            },
+            REG_SZ | REG_EXPAND_SZ => {
+                let content = words.split(|&w| w == 0).next().unwrap_or(&[]);
+                Ok(vec![OsString::from_wide(content)])
+            },
+            _ => werr!(winerror::ERROR_BAD_FILE_TYPE)
+        }
+    }
+}
+
+#[cfg(windows)]
+impl ToRegValue for Vec<OsString> {
+    fn to_reg_value(&self) -> RegValue {
+        let mut words: Vec<u16> = Vec::new();
+        for s in self {
+            words.extend(to_utf16(s));
+        }
+        words.push(0);
+        RegValue {
+            bytes: v16_to_v8(words),
+            vtype: REG_MULTI_SZ
+        }
+    }
+}
+
+#[cfg(windows)]
+impl<'a> ToRegValue for Vec<&'a OsStr> {
+    fn to_reg_value(&self) -> RegValue {
+        let mut words: Vec<u16> = Vec::new();
+        for s in self {
+            words.extend(to_utf16(s));
+        }
+        words.push(0);
+        RegValue {
+            bytes: v16_to_v8(words),
+            vtype: REG_MULTI_SZ
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_sz_vec_string_round_trip_empty() {
+        let value = Vec::<String>::new().to_reg_value();
+        assert_eq!(value.vtype, REG_MULTI_SZ);
+        assert_eq!(value.bytes, v16_to_v8(vec![0]));
+        assert_eq!(Vec::<String>::from_reg_value(value).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn multi_sz_vec_string_round_trip_multiple_entries() {
+        let strings: Vec<String> = vec!["hello".to_owned(), "world".to_owned()];
+        let value = strings.to_reg_value();
+        assert_eq!(Vec::<String>::from_reg_value(value).unwrap(), strings);
+    }
+
+    #[test]
+    fn multi_sz_vec_string_round_trip_embedded_empty_string() {
+        let strings: Vec<String> = vec!["".to_owned(), "middle".to_owned(), "".to_owned()];
+        let value = strings.to_reg_value();
+        assert_eq!(Vec::<String>::from_reg_value(value).unwrap(), strings);
+    }
+
+    #[test]
+    fn vec_string_from_plain_reg_sz() {
+        // A plain REG_SZ isn't a list, so it should decode as a single entry.
+        let value = "just one string".to_owned().to_reg_value();
+        assert_eq!(value.vtype, REG_SZ);
+        assert_eq!(
+            Vec::<String>::from_reg_value(value).unwrap(),
+            vec!["just one string".to_owned()]
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn multi_sz_vec_os_string_round_trip() {
+        let strings: Vec<OsString> =
+            vec![OsString::from("hello"), OsString::from(""), OsString::from("world")];
+        let value = strings.to_reg_value();
+        assert_eq!(Vec::<OsString>::from_reg_value(value).unwrap(), strings);
+    }
+
+    #[test]
+    fn vec_u8_round_trip_empty() {
+        let bytes: Vec<u8> = Vec::new();
+        let value = bytes.to_reg_value();
+        assert_eq!(value.vtype, REG_BINARY);
+        assert_eq!(Vec::<u8>::from_reg_value(value).unwrap(), bytes);
+    }
+
+    #[test]
+    fn vec_u8_round_trip() {
+        let bytes: Vec<u8> = vec![0, 1, 2, 255, 0, 128];
+        let value = bytes.to_reg_value();
+        assert_eq!(Vec::<u8>::from_reg_value(value).unwrap(), bytes);
+    }
+
+    #[test]
+    fn slice_u8_to_reg_value_matches_vec() {
+        let bytes: &[u8] = &[1, 2, 3];
+        let value = bytes.to_reg_value();
+        assert_eq!(value.vtype, REG_BINARY);
+        assert_eq!(Vec::<u8>::from_reg_value(value).unwrap(), bytes.to_vec());
+    }
+}